@@ -2,16 +2,257 @@ use actix_files::NamedFile;
 use actix_web::{middleware, web, App, Error, HttpRequest, HttpResponse, HttpServer, Result};
 use chrono::prelude::Utc;
 use chrono::SecondsFormat;
-use image::Luma;
+use image::{DynamicImage, ImageOutputFormat, Rgb};
 use json::JsonValue;
-use qrcode::QrCode;
+use qrcode::render::svg;
+use qrcode::{EcLevel, QrCode};
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::io::Cursor;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
+const DEFAULT_TTL_SECONDS: u64 = 24 * 60 * 60;
+const REAPER_INTERVAL_SECONDS: u64 = 60;
+const QR_EXTENSIONS: [&str; 3] = ["png", "jpg", "svg"];
+
+// Generated QR files live in their own subdirectory rather than directly in
+// `/tmp`, so the reaper only ever touches files this service created.
+const QR_OUTPUT_DIR: &str = "/tmp/qr-codes";
+
+fn ttl_seconds() -> u64 {
+    env::var("QR_TTL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_TTL_SECONDS)
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// Every generated file name is `{created_at}-{uuid}.{ext}`, so expiry reads
+// the creation timestamp straight out of the name instead of trusting the
+// filesystem's mtime, which can be touched by copies, backups, etc.
+fn created_at_of(path: &std::path::Path) -> Option<u64> {
+    path.file_stem()?
+        .to_str()?
+        .split('-')
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn reap_expired_files(ttl: Duration) {
+    let entries = match std::fs::read_dir(QR_OUTPUT_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    let now = unix_timestamp();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_qr_file = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map_or(false, |ext| QR_EXTENSIONS.contains(&ext));
+        if !is_qr_file {
+            continue;
+        }
+
+        let age = created_at_of(&path).map(|created_at| now.saturating_sub(created_at));
+
+        if age.map_or(false, |age| age > ttl.as_secs()) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct FormMessage {
     message: String,
+    inline: Option<bool>,
+    format: Option<String>,
+    ec_level: Option<String>,
+    scale: Option<u32>,
+    quiet_zone: Option<bool>,
+    fg: Option<String>,
+    bg: Option<String>,
+}
+
+// Module scale is a pixel multiplier per QR module, so anything up to this
+// still produces a legible code while keeping the rendered image well under
+// JPEG's 65535px dimension limit and any reasonable memory budget.
+const MAX_SCALE: u32 = 64;
+
+// Resolves the rendering knobs carried on a request, rejecting anything
+// invalid up front so `api_generate` never has to `.unwrap()` user input.
+struct RenderOptions {
+    ec_level: EcLevel,
+    scale: Option<u32>,
+    quiet_zone: bool,
+    fg: Rgb<u8>,
+    bg: Rgb<u8>,
+}
+
+impl RenderOptions {
+    fn from_params(params: &FormMessage) -> Result<Self, String> {
+        let ec_level = match &params.ec_level {
+            None => EcLevel::M,
+            Some(value) => match value.trim().to_ascii_uppercase().as_str() {
+                "L" => EcLevel::L,
+                "M" => EcLevel::M,
+                "Q" => EcLevel::Q,
+                "H" => EcLevel::H,
+                _ => return Err(format!("invalid ec_level: {}", value)),
+            },
+        };
+
+        let fg = match &params.fg {
+            None => Rgb([0, 0, 0]),
+            Some(value) => parse_hex_color(value)?,
+        };
+
+        let bg = match &params.bg {
+            None => Rgb([255, 255, 255]),
+            Some(value) => parse_hex_color(value)?,
+        };
+
+        let scale = match params.scale {
+            None => None,
+            Some(scale) if (1..=MAX_SCALE).contains(&scale) => Some(scale),
+            Some(scale) => {
+                return Err(format!(
+                    "invalid scale: {} (must be between 1 and {})",
+                    scale, MAX_SCALE
+                ))
+            }
+        };
+
+        Ok(RenderOptions {
+            ec_level,
+            scale,
+            quiet_zone: params.quiet_zone.unwrap_or(true),
+            fg,
+            bg,
+        })
+    }
+}
+
+fn parse_hex_color(value: &str) -> Result<Rgb<u8>, String> {
+    let hex = value.trim_start_matches('#');
+
+    // Guard on byte length *and* that every byte is an ASCII hex digit before
+    // slicing by byte range below — a multibyte value of the right byte
+    // length (e.g. `%E2%82%ACabc`) would otherwise land mid-codepoint and
+    // panic on the `&hex[..]` slice.
+    if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("invalid color: {}", value));
+    }
+
+    let channel = |range| {
+        u8::from_str_radix(&hex[range], 16).map_err(|_| format!("invalid color: {}", value))
+    };
+
+    Ok(Rgb([channel(0..2)?, channel(2..4)?, channel(4..6)?]))
+}
+
+fn to_hex_color(color: Rgb<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.0[0], color.0[1], color.0[2])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Png,
+    Jpeg,
+    Svg,
+}
+
+impl OutputFormat {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "png" | "image/png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" | "image/jpeg" => Some(OutputFormat::Jpeg),
+            "svg" | "image/svg+xml" => Some(OutputFormat::Svg),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Svg => "svg",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+// Picks the output format from `?format=`, falling back to the `Accept`
+// header, and defaulting to PNG. Returns `None` when `?format=` names a
+// format we do not support (the `Accept` header is then not consulted), or
+// when, without `?format=`, the `Accept` header matches nothing we support,
+// so the caller can respond with 406.
+fn negotiate_format(request: &HttpRequest, params: &FormMessage) -> Option<OutputFormat> {
+    if let Some(format) = &params.format {
+        return OutputFormat::parse(format);
+    }
+
+    let accept = request
+        .headers()
+        .get(actix_web::http::header::ACCEPT)
+        .and_then(|value| value.to_str().ok());
+
+    match accept {
+        None => Some(OutputFormat::Png),
+        Some(accept) => negotiate_accept(accept),
+    }
+}
+
+// Ranks the media ranges in an `Accept` header by descending q-value (ties
+// keep the header's original order) and returns the highest-ranked one we
+// support, falling back to PNG for a `*/*`/`image/*` wildcard.
+fn negotiate_accept(accept: &str) -> Option<OutputFormat> {
+    let mut candidates: Vec<(f32, usize, &str)> = accept
+        .split(',')
+        .enumerate()
+        .map(|(index, entry)| {
+            let mut segments = entry.split(';').map(|segment| segment.trim());
+            let media_range = segments.next().unwrap_or("").trim();
+            let q = segments
+                .find_map(|segment| segment.strip_prefix("q="))
+                .and_then(|value| value.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (q, index, media_range)
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| {
+        b.0.partial_cmp(&a.0)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.1.cmp(&b.1))
+    });
+
+    candidates
+        .iter()
+        .find_map(|(_, _, media_range)| OutputFormat::parse(media_range))
+        .or_else(|| {
+            candidates
+                .iter()
+                .any(|(_, _, media_range)| *media_range == "*/*" || *media_range == "image/*")
+                .then_some(OutputFormat::Png)
+        })
 }
 
 async fn health(request: HttpRequest) -> Result<HttpResponse, Error> {
@@ -26,6 +267,7 @@ async fn health(request: HttpRequest) -> Result<HttpResponse, Error> {
         }),
         "created_at" => now.to_rfc3339_opts(SecondsFormat::Millis, false),
         "version" => env!("CARGO_PKG_VERSION"),
+        "retention_seconds" => ttl_seconds(),
     };
 
     Ok(HttpResponse::Ok()
@@ -33,24 +275,131 @@ async fn health(request: HttpRequest) -> Result<HttpResponse, Error> {
         .body(response_json.dump()))
 }
 
+enum GenerateError {
+    UnsupportedFormat(String),
+    InvalidInput(String),
+}
+
+impl GenerateError {
+    fn message(&self) -> &str {
+        match self {
+            GenerateError::UnsupportedFormat(message) => message,
+            GenerateError::InvalidInput(message) => message,
+        }
+    }
+}
+
+// Renders a single request into its output bytes, doing all of the
+// validation/negotiation that both the query-string and JSON entry points
+// share. Writing the result to disk (or streaming it inline) is left to
+// the caller, since that differs between the single and batch handlers.
+fn generate_one(
+    request: &HttpRequest,
+    params: &FormMessage,
+) -> std::result::Result<(OutputFormat, Vec<u8>), GenerateError> {
+    let format = negotiate_format(request, params)
+        .ok_or_else(|| GenerateError::UnsupportedFormat("unsupported format".to_string()))?;
+
+    let options = RenderOptions::from_params(params).map_err(GenerateError::InvalidInput)?;
+
+    let code = QrCode::with_error_correction_level(params.message.clone(), options.ec_level)
+        .map_err(|error| GenerateError::InvalidInput(format!("{}", error)))?;
+
+    let bytes = match format {
+        OutputFormat::Svg => {
+            let dark_hex = to_hex_color(options.fg);
+            let light_hex = to_hex_color(options.bg);
+
+            let mut renderer = code.render::<svg::Color>();
+            renderer
+                .quiet_zone(options.quiet_zone)
+                .dark_color(svg::Color(&dark_hex))
+                .light_color(svg::Color(&light_hex));
+            if let Some(scale) = options.scale {
+                renderer.module_dimensions(scale, scale);
+            }
+            renderer.build().into_bytes()
+        }
+        OutputFormat::Png | OutputFormat::Jpeg => {
+            let mut renderer = code.render::<Rgb<u8>>();
+            renderer
+                .quiet_zone(options.quiet_zone)
+                .dark_color(options.fg)
+                .light_color(options.bg);
+            if let Some(scale) = options.scale {
+                renderer.module_dimensions(scale, scale);
+            }
+            let image = renderer.build();
+
+            let output_format = match format {
+                OutputFormat::Jpeg => ImageOutputFormat::Jpeg(90),
+                _ => ImageOutputFormat::Png,
+            };
+
+            let mut bytes: Vec<u8> = Vec::new();
+            DynamicImage::ImageRgb8(image)
+                .write_to(&mut Cursor::new(&mut bytes), output_format)
+                .map_err(|error| GenerateError::InvalidInput(format!("{}", error)))?;
+            bytes
+        }
+    };
+
+    Ok((format, bytes))
+}
+
+fn save_qr_file(format: OutputFormat, bytes: &[u8]) -> std::result::Result<String, String> {
+    std::fs::create_dir_all(QR_OUTPUT_DIR).map_err(|error| format!("{}", error))?;
+
+    let token = Uuid::new_v4().to_simple().to_string();
+    let name = format!("{}-{}.{}", unix_timestamp(), token, format.extension());
+    let filename = format!("{}/{}", QR_OUTPUT_DIR, name);
+
+    std::fs::write(&filename, bytes).map_err(|error| format!("{}", error))?;
+
+    Ok(format!("/static/{}", name))
+}
+
 async fn api_generate(
-    _request: HttpRequest,
+    request: HttpRequest,
     params: web::Query<FormMessage>,
 ) -> Result<HttpResponse, Error> {
-    let token = Uuid::new_v4().to_simple().to_string();
-    let name = format!("{}.png", token);
-    let filename = format!("/tmp/{}.png", token);
-    let fileurl = format!("/static/{}", name);
-    let message = &params.message;
+    let (format, bytes) = match generate_one(&request, &params) {
+        Ok(result) => result,
+        Err(error) => {
+            let response_json: JsonValue = json::object! {
+                "error" => error.message(),
+            };
+            let response = match error {
+                GenerateError::UnsupportedFormat(_) => HttpResponse::NotAcceptable(),
+                GenerateError::InvalidInput(_) => HttpResponse::BadRequest(),
+            };
+            return Ok(response
+                .content_type("application/json")
+                .body(response_json.dump()));
+        }
+    };
 
-    let code = QrCode::new(message.clone()).unwrap();
-    let image = code.render::<Luma<u8>>().build();
+    if params.inline.unwrap_or(false) {
+        return Ok(HttpResponse::Ok()
+            .content_type(format.content_type())
+            .body(bytes));
+    }
 
-    image.save(filename.clone()).unwrap();
+    let fileurl = match save_qr_file(format, &bytes) {
+        Ok(fileurl) => fileurl,
+        Err(error) => {
+            let response_json: JsonValue = json::object! {
+                "error" => error,
+            };
+            return Ok(HttpResponse::InternalServerError()
+                .content_type("application/json")
+                .body(response_json.dump()));
+        }
+    };
 
     let response_json: JsonValue = json::object! {
         "url" => fileurl,
-        "message" => message.clone(),
+        "message" => params.message.clone(),
     };
 
     Ok(HttpResponse::Ok()
@@ -58,10 +407,75 @@ async fn api_generate(
         .body(response_json.dump()))
 }
 
-async fn statifiles(request: HttpRequest) -> Result<NamedFile> {
-    let path = format!("/tmp/{}", request.match_info().query("filename"));
-    // println!("{:?}", path);
-    Ok(NamedFile::open(path)?)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GenerateBody {
+    Single(FormMessage),
+    Batch(Vec<FormMessage>),
+}
+
+async fn api_generate_batch(
+    request: HttpRequest,
+    payload: web::Json<GenerateBody>,
+) -> Result<HttpResponse, Error> {
+    let items = match payload.into_inner() {
+        GenerateBody::Single(item) => vec![item],
+        GenerateBody::Batch(items) => items,
+    };
+
+    let results: Vec<JsonValue> = items
+        .iter()
+        .map(|params| match generate_one(&request, params) {
+            Ok((format, bytes)) => match save_qr_file(format, &bytes) {
+                Ok(fileurl) => json::object! {
+                    "url" => fileurl,
+                    "message" => params.message.clone(),
+                },
+                Err(error) => json::object! {
+                    "error" => error,
+                    "message" => params.message.clone(),
+                },
+            },
+            Err(error) => json::object! {
+                "error" => error.message(),
+                "message" => params.message.clone(),
+            },
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(JsonValue::Array(results).dump()))
+}
+
+async fn statifiles(request: HttpRequest) -> Result<HttpResponse, Error> {
+    let path = format!(
+        "{}/{}",
+        QR_OUTPUT_DIR,
+        request.match_info().query("filename")
+    );
+    let file = match NamedFile::open(&path) {
+        Ok(file) => file.use_etag(true).use_last_modified(true),
+        Err(_) => {
+            let response_json: JsonValue = json::object! {
+                "error" => "not found",
+            };
+            return Ok(HttpResponse::NotFound()
+                .content_type("application/json")
+                .body(response_json.dump()));
+        }
+    };
+
+    // Each file name carries a fresh UUID and the file is never rewritten,
+    // so it is safe to cache forever; `into_response` already honors If-None-Match/If-Modified-Since
+    // and answers 304 on our behalf.
+    let mut response = file.into_response(&request)?;
+    response.headers_mut().insert(
+        actix_web::http::header::CACHE_CONTROL,
+        actix_web::http::HeaderValue::from_static("public, max-age=31536000, immutable"),
+    );
+
+    Ok(response)
 }
 
 #[actix_rt::main]
@@ -69,13 +483,25 @@ async fn main() -> std::io::Result<()> {
     env::set_var("RUST_BACKTRACE", "1");
     env_logger::init();
 
+    let ttl = Duration::from_secs(ttl_seconds());
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(Duration::from_secs(REAPER_INTERVAL_SECONDS));
+        loop {
+            interval.tick().await;
+            reap_expired_files(ttl);
+        }
+    });
+
     HttpServer::new(move || {
         App::new()
             .wrap(middleware::Logger::default())
             .service(web::resource("/health/").route(web::get().to(health)))
             .service(
-                web::scope("/api/v1")
-                    .service(web::resource("/generate/").route(web::get().to(api_generate))),
+                web::scope("/api/v1").service(
+                    web::resource("/generate/")
+                        .route(web::get().to(api_generate))
+                        .route(web::post().to(api_generate_batch)),
+                ),
             )
             .service(web::resource("/static/{filename:.*}").route(web::get().to(statifiles)))
     })
@@ -97,6 +523,49 @@ mod tests {
             .to_request();
         let resp = test::call_service(&mut app, req).await;
         assert_eq!(resp.status(), 200);
+
+        let body = match resp.response().body().as_ref() {
+            Some(actix_web::body::Body::Bytes(bytes)) => bytes,
+            _ => panic!("Response error"),
+        };
+        let data = json::parse(std::str::from_utf8(body).unwrap()).unwrap();
+        assert!(data["retention_seconds"].as_u64().is_some());
+    }
+
+    #[actix_rt::test]
+    async fn test_reap_expired_files_only_touches_qr_output_dir() {
+        std::fs::create_dir_all(QR_OUTPUT_DIR).unwrap();
+
+        let stale_name = format!("{}/{}-stale.png", QR_OUTPUT_DIR, unix_timestamp() - 3600);
+        std::fs::write(&stale_name, b"stale").unwrap();
+
+        let fresh_name = format!("{}/{}-fresh.png", QR_OUTPUT_DIR, unix_timestamp());
+        std::fs::write(&fresh_name, b"fresh").unwrap();
+
+        let unrelated_name = "/tmp/unrelated-file-not-ours.png";
+        std::fs::write(unrelated_name, b"unrelated").unwrap();
+
+        reap_expired_files(Duration::from_secs(60));
+
+        assert!(!std::path::Path::new(&stale_name).exists());
+        assert!(std::path::Path::new(&fresh_name).exists());
+        assert!(std::path::Path::new(unrelated_name).exists());
+
+        let _ = std::fs::remove_file(&fresh_name);
+        let _ = std::fs::remove_file(unrelated_name);
+    }
+
+    #[actix_rt::test]
+    async fn test_statifiles_reaped_file_is_404() {
+        let mut app = test::init_service(
+            App::new().route("/static/{filename:.*}", web::get().to(statifiles)),
+        )
+        .await;
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .uri("/static/does-not-exist.png")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 404);
     }
 
     #[actix_rt::test]
@@ -143,4 +612,266 @@ mod tests {
         let resp = test::call_service(&mut app, req).await;
         assert_eq!(resp.status(), 200);
     }
+
+    #[actix_rt::test]
+    async fn test_statifiles_not_modified_on_matching_etag() {
+        let mut app =
+            test::init_service(App::new().route("/api/v1/generate/", web::get().to(api_generate)))
+                .await;
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .uri("/api/v1/generate/?message=hello+wold")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        let body = match resp.response().body().as_ref() {
+            Some(actix_web::body::Body::Bytes(bytes)) => bytes,
+            _ => panic!("Response error"),
+        };
+        let data = json::parse(std::str::from_utf8(body).unwrap()).unwrap();
+        let url = data["url"].as_str().unwrap().to_owned();
+
+        let mut app = test::init_service(
+            App::new().route("/static/{filename:.*}", web::get().to(statifiles)),
+        )
+        .await;
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .uri(&url)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 200);
+        let etag = resp
+            .headers()
+            .get(header::ETAG)
+            .expect("first response should carry an ETag")
+            .clone();
+        assert!(resp.headers().contains_key(header::CACHE_CONTROL));
+
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .header(header::IF_NONE_MATCH, etag)
+            .uri(&url)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 304);
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_inline_ok() {
+        let mut app =
+            test::init_service(App::new().route("/api/v1/generate/", web::get().to(api_generate)))
+                .await;
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .uri("/api/v1/generate/?message=hello+wold&inline=true")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_svg_ok() {
+        let mut app =
+            test::init_service(App::new().route("/api/v1/generate/", web::get().to(api_generate)))
+                .await;
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .uri("/api/v1/generate/?message=hello+wold&inline=true&format=svg")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/svg+xml"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_svg_custom_colors_ok() {
+        let mut app =
+            test::init_service(App::new().route("/api/v1/generate/", web::get().to(api_generate)))
+                .await;
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .uri(
+                "/api/v1/generate/?message=hello+wold&inline=true\
+                 &format=svg&fg=%2300ff00&bg=%23111111",
+            )
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 200);
+
+        let body = match resp.response().body().as_ref() {
+            Some(actix_web::body::Body::Bytes(bytes)) => bytes,
+            _ => panic!("Response error"),
+        };
+        let svg = std::str::from_utf8(body).unwrap();
+        assert!(svg.contains("#00ff00"));
+        assert!(svg.contains("#111111"));
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_custom_params_ok() {
+        let mut app =
+            test::init_service(App::new().route("/api/v1/generate/", web::get().to(api_generate)))
+                .await;
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .uri(
+                "/api/v1/generate/?message=hello+wold&inline=true\
+                 &ec_level=H&scale=5&quiet_zone=false&fg=%23ff0000&bg=%23000000",
+            )
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 200);
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_invalid_ec_level() {
+        let mut app =
+            test::init_service(App::new().route("/api/v1/generate/", web::get().to(api_generate)))
+                .await;
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .uri("/api/v1/generate/?message=hello+wold&ec_level=Z")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_invalid_scale() {
+        let mut app =
+            test::init_service(App::new().route("/api/v1/generate/", web::get().to(api_generate)))
+                .await;
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .uri("/api/v1/generate/?message=hello+wold&format=jpeg&scale=400")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_invalid_color() {
+        let mut app =
+            test::init_service(App::new().route("/api/v1/generate/", web::get().to(api_generate)))
+                .await;
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .uri("/api/v1/generate/?message=hello+wold&fg=not-a-color")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_multibyte_color_is_400_not_panic() {
+        let mut app =
+            test::init_service(App::new().route("/api/v1/generate/", web::get().to(api_generate)))
+                .await;
+        // `%E2%82%ACabc` is the euro sign followed by "abc" — 6 bytes, 4
+        // chars, which used to slip past the byte-length guard and panic
+        // when sliced as if it were 6 single-byte hex digits.
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .uri("/api/v1/generate/?message=hello+wold&fg=%E2%82%ACabc")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_browser_accept_header_defaults_to_png() {
+        let mut app =
+            test::init_service(App::new().route("/api/v1/generate/", web::get().to(api_generate)))
+                .await;
+        let req = test::TestRequest::with_header(
+            header::ACCEPT,
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+        )
+        .uri("/api/v1/generate/?message=hello+wold&inline=true")
+        .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_accept_honors_q_values() {
+        let mut app =
+            test::init_service(App::new().route("/api/v1/generate/", web::get().to(api_generate)))
+                .await;
+        let req = test::TestRequest::with_header(
+            header::ACCEPT,
+            "image/jpeg;q=0.1, image/png;q=0.9",
+        )
+        .uri("/api/v1/generate/?message=hello+wold&inline=true")
+        .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_unsupported_format() {
+        let mut app =
+            test::init_service(App::new().route("/api/v1/generate/", web::get().to(api_generate)))
+                .await;
+        let req = test::TestRequest::with_header(header::CONTENT_TYPE, "aplication/json")
+            .uri("/api/v1/generate/?message=hello+wold&format=bmp")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 406);
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_post_single_ok() {
+        let mut app = test::init_service(
+            App::new().route("/api/v1/generate/", web::post().to(api_generate_batch)),
+        )
+        .await;
+        let req = test::TestRequest::post()
+            .uri("/api/v1/generate/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .set_payload(r#"{"message":"hello world"}"#)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 200);
+
+        let body = match resp.response().body().as_ref() {
+            Some(actix_web::body::Body::Bytes(bytes)) => bytes,
+            _ => panic!("Response error"),
+        };
+        let data = json::parse(std::str::from_utf8(body).unwrap()).unwrap();
+        assert!(data.is_array());
+        assert_eq!(data.len(), 1);
+        assert!(data[0]["url"].as_str().is_some());
+    }
+
+    #[actix_rt::test]
+    async fn test_api_generate_post_batch_reports_per_item_errors() {
+        let mut app = test::init_service(
+            App::new().route("/api/v1/generate/", web::post().to(api_generate_batch)),
+        )
+        .await;
+        let req = test::TestRequest::post()
+            .uri("/api/v1/generate/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .set_payload(
+                r#"[{"message":"hello world"},{"message":"broken","ec_level":"Z"}]"#,
+            )
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 200);
+
+        let body = match resp.response().body().as_ref() {
+            Some(actix_web::body::Body::Bytes(bytes)) => bytes,
+            _ => panic!("Response error"),
+        };
+        let data = json::parse(std::str::from_utf8(body).unwrap()).unwrap();
+        assert_eq!(data.len(), 2);
+        assert!(data[0]["url"].as_str().is_some());
+        assert!(data[1]["error"].as_str().is_some());
+    }
 }